@@ -1,7 +1,11 @@
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io;
-use std::path::{Path};
+use std::path::{Path, PathBuf};
+
+/// 撤销日志文件名，始终被排除在重命名候选之外
+const UNDO_LOG_FILE_NAME: &str = ".batch-rename-undo.json";
 
 /// 批量重命名工具
 #[derive(Parser)]
@@ -15,11 +19,46 @@ struct Cli {
     #[clap(short, long)]
     verbose: bool,
 
+    /// 递归处理目录下的所有子目录
+    #[clap(short, long)]
+    recursive: bool,
+
+    /// 仅处理目录，跳过文件
+    #[clap(long, conflicts_with = "files_only")]
+    dirs_only: bool,
+
+    /// 仅处理文件，跳过目录
+    #[clap(long, conflicts_with = "dirs_only")]
+    files_only: bool,
+
+    /// 仅处理匹配该通配符模式的文件名（如 "*.jpg"）
+    #[clap(long)]
+    include: Option<String>,
+
+    /// 排除匹配该通配符模式的文件名（如 "*.tmp"）
+    #[clap(long)]
+    exclude: Option<String>,
+
+    /// 出现重命名冲突时的处理方式
+    #[clap(long, value_enum, default_value_t = ConflictPolicy::Abort)]
+    on_conflict: ConflictPolicy,
+
     /// 执行操作的子命令
     #[clap(subcommand)]
     command: Commands,
 }
 
+/// 重命名冲突的处理策略
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ConflictPolicy {
+    /// 跳过发生冲突的条目，仅执行其余部分
+    Skip,
+    /// 只要存在冲突就中止整个批次
+    Abort,
+    /// 忽略冲突，允许覆盖已存在的文件
+    Overwrite,
+}
+
 #[derive(Subcommand)]
 enum Commands {
     /// 删除文件名中的指定字符串
@@ -64,6 +103,32 @@ enum Commands {
         #[clap(help = "替换后的字符串")]
         replacement: String,
     },
+
+    /// 将文件名主体（最后一个 "." 之前的部分）规范化为安全字符集 [0-9A-Za-z._-]，扩展名不做字符替换
+    Sanitize {
+        /// 将结果转换为小写
+        #[clap(short, long)]
+        lowercase: bool,
+    },
+
+    /// 按模板批量重命名，支持 {name}/{ext}/{n}/{date}/{datetime} 等占位符
+    Template {
+        /// 模板字符串，如 "{date}_{n}.{ext}"
+        #[clap(help = "重命名模板，支持 {name}、{ext}、{n}、{date}、{datetime} 占位符")]
+        pattern: String,
+
+        /// 序号计数器 {n} 的位数，不足时用 0 补齐
+        #[clap(long, default_value_t = 3)]
+        pad: usize,
+    },
+
+    /// 撤销目标目录下最近一次成功的批量重命名
+    Undo,
+}
+
+/// `Sanitize` 子命令的行为配置
+struct SanitizeOptions {
+    lowercase: bool,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -82,49 +147,235 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         println!("目标目录: {:?}", target_dir);
     }
 
+    let options = TraverseOptions {
+        recursive: cli.recursive,
+        dirs_only: cli.dirs_only,
+        files_only: cli.files_only,
+    };
+    let filter = NameFilter::new(cli.include.as_deref(), cli.exclude.as_deref())?;
+
     match &cli.command {
         Commands::Remove { pattern } => {
             if cli.verbose {
                 println!("删除字符串: \"{}\"", pattern);
             }
-            batch_rename(target_dir, |name| remove_string(name, pattern), cli.verbose)?;
+            batch_rename(target_dir, |name| remove_string(name, pattern), cli.verbose, &options, &filter, cli.on_conflict)?;
         }
         Commands::Replace { old, new } => {
             if cli.verbose {
                 println!("替换 \"{}\" 为 \"{}\"", old, new);
             }
-            batch_rename(target_dir, |name| replace_string(name, old, new), cli.verbose)?;
+            batch_rename(target_dir, |name| replace_string(name, old, new), cli.verbose, &options, &filter, cli.on_conflict)?;
         }
         Commands::AddPrefix { prefix } => {
             if cli.verbose {
                 println!("添加前缀: \"{}\"", prefix);
             }
-            batch_rename(target_dir, |name| add_prefix(name, prefix), cli.verbose)?;
+            batch_rename(target_dir, |name| add_prefix(name, prefix), cli.verbose, &options, &filter, cli.on_conflict)?;
         }
         Commands::AddSuffix { suffix } => {
             if cli.verbose {
                 println!("添加后缀: \"{}\"", suffix);
             }
-            batch_rename(target_dir, |name| add_suffix(name, suffix), cli.verbose)?;
+            batch_rename(target_dir, |name| add_suffix(name, suffix), cli.verbose, &options, &filter, cli.on_conflict)?;
         }
         Commands::RegexReplace { pattern, replacement } => {
             if cli.verbose {
                 println!("正则替换: \"{}\" -> \"{}\"", pattern, replacement);
             }
-            batch_rename(target_dir, |name| regex_replace(name, pattern, replacement), cli.verbose)?;
+            batch_rename(target_dir, |name| regex_replace(name, pattern, replacement), cli.verbose, &options, &filter, cli.on_conflict)?;
+        }
+        Commands::Sanitize { lowercase } => {
+            if cli.verbose {
+                println!("规范化文件名为安全字符集");
+            }
+            let sanitize_opts = SanitizeOptions { lowercase: *lowercase };
+            batch_rename(target_dir, |name| sanitize_name(name, &sanitize_opts), cli.verbose, &options, &filter, cli.on_conflict)?;
+        }
+        Commands::Template { pattern, pad } => {
+            if cli.verbose {
+                println!("按模板重命名: \"{}\"", pattern);
+            }
+            template_rename(target_dir, pattern, *pad, cli.verbose, &options, &filter, cli.on_conflict)?;
+        }
+        Commands::Undo => {
+            if cli.verbose {
+                println!("撤销最近一次批量重命名");
+            }
+            undo_last(target_dir, cli.verbose, cli.on_conflict)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// 目录遍历方式的配置
+struct TraverseOptions {
+    recursive: bool,
+    dirs_only: bool,
+    files_only: bool,
+}
+
+/// 基于通配符的文件名过滤器
+struct NameFilter {
+    include: Option<regex::Regex>,
+    exclude: Option<regex::Regex>,
+}
+
+impl NameFilter {
+    fn new(include: Option<&str>, exclude: Option<&str>) -> Result<Self, Box<dyn std::error::Error>> {
+        let include = include
+            .map(|glob| regex::Regex::new(&glob_to_regex(glob)))
+            .transpose()?;
+        let exclude = exclude
+            .map(|glob| regex::Regex::new(&glob_to_regex(glob)))
+            .transpose()?;
+        Ok(Self { include, exclude })
+    }
+
+    /// 判断文件名是否应该参与本次重命名
+    fn matches(&self, name: &str) -> bool {
+        if let Some(include) = &self.include {
+            if !include.is_match(name) {
+                return false;
+            }
+        }
+        if let Some(exclude) = &self.exclude {
+            if exclude.is_match(name) {
+                return false;
+            }
         }
+        true
     }
+}
+
+/// 将通配符模式（`*`、`?`）转换为锚定的正则表达式
+fn glob_to_regex(glob: &str) -> String {
+    let mut pattern = String::from("^");
+    for c in glob.chars() {
+        match c {
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            '.' => pattern.push_str("\\."),
+            '\\' | '+' | '(' | ')' | '|' | '[' | ']' | '{' | '}' | '^' | '$' => {
+                pattern.push('\\');
+                pattern.push(c);
+            }
+            _ => pattern.push(c),
+        }
+    }
+    pattern.push('$');
+    pattern
+}
+
+/// 在 `changes` 中查找重命名冲突：多个来源映射到同一目标名，
+/// 或目标路径已存在于磁盘上且不属于本批次自身
+fn detect_conflicts(changes: &[(String, String, PathBuf)]) -> Vec<(PathBuf, Vec<PathBuf>)> {
+    let mut targets: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+    for (_, new_name, old_path) in changes {
+        let parent = old_path.parent().unwrap_or_else(|| Path::new("."));
+        targets.entry(parent.join(new_name)).or_default().push(old_path.clone());
+    }
+
+    let old_paths: HashSet<&PathBuf> = changes.iter().map(|(_, _, old_path)| old_path).collect();
 
+    let mut conflicts: Vec<_> = targets
+        .into_iter()
+        .filter(|(target, sources)| sources.len() > 1 || (target.exists() && !old_paths.contains(target)))
+        .collect();
+    conflicts.sort_by(|a, b| a.0.cmp(&b.0));
+    conflicts
+}
+
+/// 根据 `on_conflict` 策略处理检测到的冲突
+///
+/// 返回 `None` 表示整个批次已中止，调用方应直接结束本次操作。
+fn resolve_conflicts(
+    changes: Vec<(String, String, PathBuf)>,
+    on_conflict: ConflictPolicy,
+) -> Option<Vec<(String, String, PathBuf)>> {
+    let conflicts = detect_conflicts(&changes);
+    if conflicts.is_empty() {
+        return Some(changes);
+    }
+
+    println!("\n检测到 {} 处重命名冲突:", conflicts.len());
+    for (target, sources) in &conflicts {
+        let sources: Vec<_> = sources.iter().map(|p| p.display().to_string()).collect();
+        println!("  [{}] -> {}", sources.join(", "), target.display());
+    }
+
+    match on_conflict {
+        ConflictPolicy::Abort => {
+            println!("存在冲突，操作已中止");
+            None
+        }
+        ConflictPolicy::Overwrite => {
+            println!("已忽略冲突，将覆盖已存在的文件");
+            Some(changes)
+        }
+        ConflictPolicy::Skip => {
+            let conflicting: HashSet<PathBuf> = conflicts
+                .into_iter()
+                .flat_map(|(_, sources)| sources.into_iter())
+                .collect();
+            println!("已跳过冲突条目");
+            Some(
+                changes
+                    .into_iter()
+                    .filter(|(_, _, old_path)| !conflicting.contains(old_path))
+                    .collect(),
+            )
+        }
+    }
+}
+
+/// 递归收集 `dir` 下的重命名候选路径
+///
+/// 目录会在其子项之后被加入结果列表，保证更深层的路径排在前面，
+/// 这样按此顺序先重命名子项、后重命名父目录时，不会使已收集的子路径失效。
+fn collect_candidates(dir: &Path, options: &TraverseOptions) -> io::Result<Vec<PathBuf>> {
+    let mut candidates = Vec::new();
+    collect_candidates_into(dir, options, &mut candidates)?;
+    Ok(candidates)
+}
+
+fn collect_candidates_into(
+    dir: &Path,
+    options: &TraverseOptions,
+    candidates: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)?.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.file_name().and_then(|n| n.to_str()) == Some(UNDO_LOG_FILE_NAME) {
+            continue;
+        }
+        if path.is_dir() {
+            if options.recursive {
+                collect_candidates_into(&path, options, candidates)?;
+            }
+            if !options.files_only {
+                candidates.push(path);
+            }
+        } else if !options.dirs_only {
+            candidates.push(path);
+        }
+    }
     Ok(())
 }
 
-fn batch_rename<F>(dir: &Path, rename_func: F, verbose: bool) -> Result<(), Box<dyn std::error::Error>>
+fn batch_rename<F>(
+    dir: &Path,
+    rename_func: F,
+    verbose: bool,
+    options: &TraverseOptions,
+    filter: &NameFilter,
+    on_conflict: ConflictPolicy,
+) -> Result<(), Box<dyn std::error::Error>>
 where
     F: Fn(&str) -> String,
 {
-    let entries: Vec<_> = fs::read_dir(dir)?
-        .filter_map(|entry| entry.ok())
-        .collect();
+    let entries = collect_candidates(dir, options)?;
 
     if entries.is_empty() {
         println!("目录为空");
@@ -138,10 +389,12 @@ where
     let mut changes = Vec::new();
 
     // 预览所有更改
-    for entry in &entries {
-        let path = entry.path();
+    for path in &entries {
         if let Some(file_name) = path.file_name() {
             if let Some(name_str) = file_name.to_str() {
+                if !filter.matches(name_str) {
+                    continue;
+                }
                 let new_name = rename_func(name_str);
                 if new_name != name_str {
                     changes.push((name_str.to_string(), new_name, path.clone()));
@@ -150,6 +403,104 @@ where
         }
     }
 
+    confirm_and_execute(dir, changes, verbose, on_conflict, true)
+}
+
+/// 按 `pattern` 模板重命名 `dir` 下的候选文件
+///
+/// 与 `batch_rename` 不同，模板需要访问完整路径（读取修改时间）以及
+/// 跨候选列表递增的序号，因此无法复用只接受 `Fn(&str) -> String` 的重命名闭包，
+/// 这里单独走一条重命名路径，最终仍汇入共用的 `confirm_and_execute`。
+fn template_rename(
+    dir: &Path,
+    pattern: &str,
+    pad: usize,
+    verbose: bool,
+    options: &TraverseOptions,
+    filter: &NameFilter,
+    on_conflict: ConflictPolicy,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut entries = collect_candidates(dir, options)?;
+    entries.sort();
+
+    if entries.is_empty() {
+        println!("目录为空");
+        return Ok(());
+    }
+
+    if verbose {
+        println!("找到 {} 个文件/目录", entries.len());
+    }
+
+    let mut changes = Vec::new();
+    let mut counter: u64 = 1;
+
+    for path in &entries {
+        let file_name = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name,
+            None => continue,
+        };
+        if !filter.matches(file_name) {
+            continue;
+        }
+
+        let new_name = format_template(pattern, path, counter, pad);
+        counter += 1;
+
+        if new_name != file_name {
+            changes.push((file_name.to_string(), new_name, path.clone()));
+        }
+    }
+
+    confirm_and_execute(dir, changes, verbose, on_conflict, true)
+}
+
+/// 展开模板中的 `{name}`、`{ext}`、`{n}`、`{date}`、`{datetime}` 占位符
+///
+/// `{n}` 按 `pad` 位数补零，`{date}`/`{datetime}` 取自文件的修改时间。
+fn format_template(pattern: &str, path: &Path, index: u64, pad: usize) -> String {
+    let name = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+
+    let (date, datetime) = match fs::metadata(path).and_then(|m| m.modified()) {
+        Ok(modified) => {
+            let local: chrono::DateTime<chrono::Local> = modified.into();
+            (
+                local.format("%Y-%m-%d").to_string(),
+                local.format("%a %b %e %T %Y").to_string(),
+            )
+        }
+        Err(_) => (String::new(), String::new()),
+    };
+
+    pattern
+        .replace("{name}", name)
+        .replace("{ext}", ext)
+        .replace("{datetime}", &datetime)
+        .replace("{date}", &date)
+        .replace("{n}", &format!("{:0width$}", index, width = pad))
+}
+
+/// 重命名的通用流程：冲突检测、预览、确认、执行重命名
+///
+/// `batch_rename` 与基于模板的重命名路径共用这段逻辑。
+fn confirm_and_execute(
+    dir: &Path,
+    changes: Vec<(String, String, PathBuf)>,
+    verbose: bool,
+    on_conflict: ConflictPolicy,
+    record_log: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if changes.is_empty() {
+        println!("没有需要更改的文件名");
+        return Ok(());
+    }
+
+    let changes = match resolve_conflicts(changes, on_conflict) {
+        Some(changes) => changes,
+        None => return Ok(()),
+    };
+
     if changes.is_empty() {
         println!("没有需要更改的文件名");
         return Ok(());
@@ -172,9 +523,10 @@ where
     // 执行重命名
     let mut success_count = 0;
     let mut error_count = 0;
+    let mut executed = Vec::new();
 
     for (old_name, new_name, old_path) in changes {
-        let parent = old_path.parent().unwrap_or(dir);
+        let parent = old_path.parent().unwrap_or_else(|| Path::new("."));
         let new_path = parent.join(&new_name);
 
         match fs::rename(&old_path, &new_path) {
@@ -182,6 +534,7 @@ where
                 if verbose {
                     println!("✓ {} -> {}", old_name, new_name);
                 }
+                executed.push(UndoEntry { old_path, new_path });
                 success_count += 1;
             }
             Err(e) => {
@@ -192,9 +545,67 @@ where
     }
 
     println!("\n完成! 成功: {}, 失败: {}", success_count, error_count);
+
+    if record_log && !executed.is_empty() {
+        if let Err(e) = write_undo_log(dir, &executed) {
+            println!("警告: 撤销记录写入失败: {}", e);
+        }
+    }
+
     Ok(())
 }
 
+/// 撤销日志中记录的单条重命名
+#[derive(serde::Serialize, serde::Deserialize)]
+struct UndoEntry {
+    old_path: PathBuf,
+    new_path: PathBuf,
+}
+
+fn undo_log_path(dir: &Path) -> PathBuf {
+    dir.join(UNDO_LOG_FILE_NAME)
+}
+
+fn write_undo_log(dir: &Path, entries: &[UndoEntry]) -> Result<(), Box<dyn std::error::Error>> {
+    let json = serde_json::to_string_pretty(entries)?;
+    fs::write(undo_log_path(dir), json)?;
+    Ok(())
+}
+
+fn read_undo_log(dir: &Path) -> Result<Vec<UndoEntry>, Box<dyn std::error::Error>> {
+    let path = undo_log_path(dir);
+    let content = fs::read_to_string(&path).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => format!("未找到可撤销的记录: {:?}", path),
+        _ => format!("读取撤销记录失败: {:?} ({})", path, e),
+    })?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// 撤销 `dir` 下最近一次成功的批量重命名
+///
+/// 读取撤销日志，按与原始执行相反的顺序将每个目标路径改回原名，
+/// 复用 `confirm_and_execute` 的预览、确认与冲突检测流程。
+fn undo_last(dir: &Path, verbose: bool, on_conflict: ConflictPolicy) -> Result<(), Box<dyn std::error::Error>> {
+    let entries = read_undo_log(dir)?;
+
+    if entries.is_empty() {
+        println!("撤销记录为空");
+        return Ok(());
+    }
+
+    let changes = entries
+        .into_iter()
+        .rev()
+        .filter_map(|entry| {
+            let old_name = entry.new_path.file_name()?.to_str()?.to_string();
+            let new_name = entry.old_path.file_name()?.to_str()?.to_string();
+            Some((old_name, new_name, entry.new_path))
+        })
+        .collect();
+
+    confirm_and_execute(dir, changes, verbose, on_conflict, false)
+}
+
 fn remove_string(name: &str, pattern: &str) -> String {
     name.replace(pattern, "")
 }
@@ -221,4 +632,33 @@ fn regex_replace(name: &str, pattern: &str, replacement: &str) -> String {
         Ok(re) => re.replace_all(name, replacement).to_string(),
         Err(_) => name.to_string(), // 如果正则表达式无效，返回原名
     }
+}
+
+fn sanitize_name(name: &str, opts: &SanitizeOptions) -> String {
+    let (base, ext) = match name.rfind('.') {
+        Some(dot_index) => name.split_at(dot_index),
+        None => (name, ""),
+    };
+
+    let mut sanitized = String::new();
+    let mut last_was_replacement = false;
+    for c in base.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' || c == '-' {
+            sanitized.push(c);
+            last_was_replacement = false;
+        } else if !last_was_replacement {
+            sanitized.push('-');
+            last_was_replacement = true;
+        }
+    }
+
+    let sanitized = sanitized.trim_start_matches(['-', '.']);
+    let sanitized = if sanitized.is_empty() { "-" } else { sanitized };
+
+    let result = format!("{}{}", sanitized, ext);
+    if opts.lowercase {
+        result.to_lowercase()
+    } else {
+        result
+    }
 }
\ No newline at end of file